@@ -0,0 +1,547 @@
+//! Import/export of vanilla Minecraft Anvil region (`.mca`) files.
+//!
+//! This only understands the pre-1.18 chunk layout (block data nested under
+//! a `Level` compound, non-negative section `Y`) and translates block
+//! states through a small best-effort name table: unrecognised block names
+//! fall back to air rather than failing the whole region.
+//!
+//! A region file only covers its own 32x32 chunk grid, so its absolute
+//! position is taken from the standard `r.<x>.<z>.mca` filename convention
+//! rather than assumed to be region `(0, 0)`.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use flate2::Compression;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use mcrs::{Block, Coordinate};
+
+use crate::nbt::{self, Value};
+
+const SECTOR_SIZE: usize = 4096;
+const HEADER_SIZE: usize = 2 * SECTOR_SIZE;
+const CHUNK_SIDE: i32 = 16;
+const SECTION_HEIGHT: i32 = 16;
+const BLOCKS_PER_SECTION: usize = CHUNK_SIDE as usize * SECTION_HEIGHT as usize * CHUNK_SIDE as usize;
+
+/// Parses the region coordinates encoded in a vanilla `r.<x>.<z>.mca`
+/// filename; Anvil only ever stores one region's 32x32 chunks per file, so
+/// this is how a chunk's region-relative entry maps to an absolute position
+fn region_coords_from_filename(path: &Path) -> Result<(i32, i32)> {
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .with_context(|| format!("Region filename is not valid UTF-8: {}", path.display()))?;
+
+    let mut parts = name.split('.');
+    let (Some("r"), Some(x), Some(z), Some("mca")) = (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        bail!("Region filename `{name}` does not match the `r.<x>.<z>.mca` convention");
+    };
+
+    let region_x: i32 = x
+        .parse()
+        .with_context(|| format!("Invalid region X coordinate in filename `{name}`"))?;
+    let region_z: i32 = z
+        .parse()
+        .with_context(|| format!("Invalid region Z coordinate in filename `{name}`"))?;
+    Ok((region_x, region_z))
+}
+
+/// Normalizes two corners so `origin` is componentwise <= `bound`, matching
+/// the "order does not matter" guarantee `clear`/`save`/`load` already give
+fn normalize_bounds(a: Coordinate, b: Coordinate) -> (Coordinate, Coordinate) {
+    (
+        Coordinate::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+        Coordinate::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+    )
+}
+
+/// Reads every block stored in `path` whose world coordinate falls within
+/// `origin..bound`, in no particular order
+///
+/// Order of `origin`/`bound` does not matter; they are normalized
+pub fn import_region(path: &Path, origin: Coordinate, bound: Coordinate) -> Result<Vec<(Coordinate, Block)>> {
+    let (origin, bound) = normalize_bounds(origin, bound);
+    let (region_x, region_z) = region_coords_from_filename(path)?;
+
+    let data = fs::read(path).with_context(|| format!("Failed to read region file {}", path.display()))?;
+    if data.len() < HEADER_SIZE {
+        bail!("Region file is smaller than its header tables");
+    }
+
+    let mut blocks = Vec::new();
+
+    for entry in 0..1024usize {
+        let header = &data[entry * 4..entry * 4 + 4];
+        let sector_offset = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+        let sector_count = header[3] as usize;
+        if sector_offset == 0 && sector_count == 0 {
+            continue;
+        }
+
+        let start = sector_offset * SECTOR_SIZE;
+        let end = start + sector_count * SECTOR_SIZE;
+        if end > data.len() || start + 5 > data.len() {
+            bail!("Chunk at region entry {entry} points past the end of the file");
+        }
+
+        let length = u32::from_be_bytes(data[start..start + 4].try_into().unwrap()) as usize;
+        let compression = data[start + 4];
+        let payload_start = start + 5;
+        let payload_end = payload_start + length.saturating_sub(1);
+        if payload_end > data.len() {
+            bail!("Truncated chunk payload at region entry {entry}");
+        }
+        let payload = &data[payload_start..payload_end];
+
+        let chunk_x = region_x * 32 + (entry % 32) as i32;
+        let chunk_z = region_z * 32 + (entry / 32) as i32;
+        let root = decompress_chunk(payload, compression)?;
+        read_chunk_blocks(&root, chunk_x, chunk_z, origin, bound, &mut blocks)?;
+    }
+
+    Ok(blocks)
+}
+
+/// Writes blocks yielded by `next_block` (assumed to fully cover
+/// `origin..bound`) to `path` as a fresh Anvil region file, creating parent
+/// sectors as needed
+///
+/// `next_block` is polled until it returns `Ok(None)`, so callers can stream
+/// blocks in directly (e.g. from a [`mcrs::chunk::ChunkStream`]) instead of
+/// collecting them into a `Vec` first
+///
+/// Order of `origin`/`bound` does not matter; they are normalized
+pub fn export_region(
+    path: &Path,
+    origin: Coordinate,
+    bound: Coordinate,
+    mut next_block: impl FnMut() -> Result<Option<(Coordinate, Block)>>,
+) -> Result<()> {
+    let (origin, bound) = normalize_bounds(origin, bound);
+    let (region_x, region_z) = region_coords_from_filename(path)?;
+
+    let mut chunks: HashMap<(i32, i32), HashMap<i32, Vec<Block>>> = HashMap::new();
+
+    while let Some((coord, block)) = next_block()? {
+        if coord.x < origin.x || coord.y < origin.y || coord.z < origin.z {
+            continue;
+        }
+        if coord.x >= bound.x || coord.y >= bound.y || coord.z >= bound.z {
+            continue;
+        }
+
+        let chunk_x = coord.x.div_euclid(CHUNK_SIDE);
+        let chunk_z = coord.z.div_euclid(CHUNK_SIDE);
+        let section_y = coord.y.div_euclid(SECTION_HEIGHT);
+
+        let local_x = coord.x.rem_euclid(CHUNK_SIDE);
+        let local_y = coord.y.rem_euclid(SECTION_HEIGHT);
+        let local_z = coord.z.rem_euclid(CHUNK_SIDE);
+        let index = (local_y * CHUNK_SIDE * CHUNK_SIDE + local_z * CHUNK_SIDE + local_x) as usize;
+
+        let section = chunks
+            .entry((chunk_x, chunk_z))
+            .or_default()
+            .entry(section_y)
+            .or_insert_with(|| vec![Block::AIR; BLOCKS_PER_SECTION]);
+        section[index] = block;
+    }
+
+    let mut location_table = [0u8; SECTOR_SIZE];
+    let mut timestamp_table = [0u8; SECTOR_SIZE];
+    let mut body = Vec::new();
+    let mut next_sector = 2u32;
+
+    let mut chunk_keys: Vec<_> = chunks.keys().copied().collect();
+    chunk_keys.sort();
+
+    for (chunk_x, chunk_z) in chunk_keys {
+        let local_x = chunk_x - region_x * 32;
+        let local_z = chunk_z - region_z * 32;
+        if !(0..32).contains(&local_x) || !(0..32).contains(&local_z) {
+            bail!(
+                "Chunk ({chunk_x}, {chunk_z}) falls outside region ({region_x}, {region_z}) covered by {}",
+                path.display()
+            );
+        }
+
+        let sections = &chunks[&(chunk_x, chunk_z)];
+        let mut section_values = Vec::new();
+        let mut section_ys: Vec<_> = sections.keys().copied().collect();
+        section_ys.sort();
+        for y in section_ys {
+            section_values.push(build_section_nbt(y, &sections[&y]));
+        }
+
+        let root = Value::Compound(vec![(
+            "Level".to_string(),
+            Value::Compound(vec![
+                ("xPos".to_string(), Value::Int(chunk_x)),
+                ("zPos".to_string(), Value::Int(chunk_z)),
+                ("Sections".to_string(), Value::List(section_values)),
+            ]),
+        )]);
+
+        let mut raw = Vec::new();
+        nbt::write_root(&mut raw, &root)?;
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&raw)?;
+        let compressed = encoder.finish()?;
+
+        let chunk_length = (compressed.len() + 1) as u32;
+        body.extend_from_slice(&chunk_length.to_be_bytes());
+        body.push(2); // zlib
+        body.extend_from_slice(&compressed);
+        while body.len() % SECTOR_SIZE != 0 {
+            body.push(0);
+        }
+
+        let sector_count = (compressed.len() + 5).div_ceil(SECTOR_SIZE);
+        let entry = (local_x + local_z * 32) as usize;
+        let offset_bytes = next_sector.to_be_bytes();
+        location_table[entry * 4] = offset_bytes[1];
+        location_table[entry * 4 + 1] = offset_bytes[2];
+        location_table[entry * 4 + 2] = offset_bytes[3];
+        location_table[entry * 4 + 3] = sector_count as u8;
+        timestamp_table[entry * 4..entry * 4 + 4].copy_from_slice(&0u32.to_be_bytes());
+
+        next_sector += sector_count as u32;
+    }
+
+    let mut file = Vec::with_capacity(HEADER_SIZE + body.len());
+    file.extend_from_slice(&location_table);
+    file.extend_from_slice(&timestamp_table);
+    file.extend_from_slice(&body);
+
+    fs::write(path, file).with_context(|| format!("Failed to write region file {}", path.display()))?;
+    Ok(())
+}
+
+fn decompress_chunk(payload: &[u8], compression: u8) -> Result<Value> {
+    let mut raw = Vec::new();
+    match compression {
+        1 => GzDecoder::new(payload).read_to_end(&mut raw)?,
+        2 => ZlibDecoder::new(payload).read_to_end(&mut raw)?,
+        3 => {
+            raw.extend_from_slice(payload);
+            raw.len()
+        }
+        other => bail!("Unsupported chunk compression type {other}"),
+    };
+    nbt::read_root(&mut Cursor::new(raw))
+}
+
+fn read_chunk_blocks(
+    root: &Value,
+    chunk_x: i32,
+    chunk_z: i32,
+    origin: Coordinate,
+    bound: Coordinate,
+    out: &mut Vec<(Coordinate, Block)>,
+) -> Result<()> {
+    let Some(level) = root.get("Level") else {
+        return Ok(());
+    };
+    let Some(sections) = level.get("Sections").and_then(Value::as_list) else {
+        return Ok(());
+    };
+
+    for section in sections {
+        let Some(y) = section.get("Y").and_then(Value::as_byte) else {
+            continue;
+        };
+        let Some(palette) = section.get("Palette").and_then(Value::as_list) else {
+            continue;
+        };
+
+        let palette: Vec<Block> = palette
+            .iter()
+            .map(|entry| {
+                let name = entry.get("Name").and_then(Value::as_str).unwrap_or("minecraft:air");
+                legacy_block_for_name(name)
+            })
+            .collect();
+
+        let block_states = section.get("BlockStates").and_then(Value::as_long_array);
+
+        // A uniform-block section (all-air above the build height, solid
+        // stone/bedrock near the bottom, etc.) has a single-entry palette,
+        // and vanilla omits (or empties) `BlockStates` entirely rather than
+        // writing out a redundant index for every block
+        if palette.len() <= 1 || block_states.map_or(true, <[i64]>::is_empty) {
+            let block = palette.first().copied().unwrap_or(Block::AIR);
+            for i in 0..BLOCKS_PER_SECTION {
+                let coord = section_block_coord(chunk_x, chunk_z, y as i32, i);
+                if is_within_bounds(coord, origin, bound) {
+                    out.push((coord, block));
+                }
+            }
+            continue;
+        }
+        let block_states = block_states.unwrap();
+
+        let bits_per_block = (usize::BITS - (palette.len().max(2) - 1).leading_zeros()).max(4) as usize;
+        let indices_per_long = 64 / bits_per_block;
+        let mask = (1u64 << bits_per_block) - 1;
+
+        for i in 0..BLOCKS_PER_SECTION {
+            let long_index = i / indices_per_long;
+            let Some(&long) = block_states.get(long_index) else {
+                break;
+            };
+            let bit_offset = (i % indices_per_long) * bits_per_block;
+            let palette_index = ((long as u64) >> bit_offset) & mask;
+            let Some(&block) = palette.get(palette_index as usize) else {
+                continue;
+            };
+
+            let coord = section_block_coord(chunk_x, chunk_z, y as i32, i);
+            if is_within_bounds(coord, origin, bound) {
+                out.push((coord, block));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Converts a section-local block index (the bit-packed `BlockStates`
+/// ordering: X, then Z, then Y) into its absolute world coordinate
+fn section_block_coord(chunk_x: i32, chunk_z: i32, section_y: i32, index: usize) -> Coordinate {
+    let local_x = (index & 0xf) as i32;
+    let local_z = ((index >> 4) & 0xf) as i32;
+    let local_y = (index >> 8) as i32;
+
+    Coordinate::new(
+        chunk_x * CHUNK_SIDE + local_x,
+        section_y * SECTION_HEIGHT + local_y,
+        chunk_z * CHUNK_SIDE + local_z,
+    )
+}
+
+fn is_within_bounds(coord: Coordinate, origin: Coordinate, bound: Coordinate) -> bool {
+    coord.x >= origin.x
+        && coord.y >= origin.y
+        && coord.z >= origin.z
+        && coord.x < bound.x
+        && coord.y < bound.y
+        && coord.z < bound.z
+}
+
+fn build_section_nbt(y: i32, blocks: &[Block]) -> Value {
+    let mut palette: Vec<Block> = Vec::new();
+    let mut indices = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        let index = match palette.iter().position(|existing| existing == block) {
+            Some(index) => index,
+            None => {
+                palette.push(*block);
+                palette.len() - 1
+            }
+        };
+        indices.push(index);
+    }
+
+    let bits_per_block = (usize::BITS - (palette.len().max(2) - 1).leading_zeros()).max(4) as usize;
+    let indices_per_long = 64 / bits_per_block;
+    let mask = (1u64 << bits_per_block) - 1;
+    let long_count = indices.len().div_ceil(indices_per_long);
+    let mut longs = vec![0i64; long_count];
+
+    for (i, &index) in indices.iter().enumerate() {
+        let long_index = i / indices_per_long;
+        let bit_offset = (i % indices_per_long) * bits_per_block;
+        longs[long_index] |= ((index as u64 & mask) << bit_offset) as i64;
+    }
+
+    let palette_values = palette
+        .iter()
+        .map(|block| {
+            Value::Compound(vec![(
+                "Name".to_string(),
+                Value::String(name_for_legacy_block(*block).to_string()),
+            )])
+        })
+        .collect();
+
+    Value::Compound(vec![
+        ("Y".to_string(), Value::Byte(y as i8)),
+        ("Palette".to_string(), Value::List(palette_values)),
+        ("BlockStates".to_string(), Value::LongArray(longs)),
+    ])
+}
+
+/// Best-effort name -> legacy (pre-flattening) id/modifier table covering
+/// common vanilla blocks; anything unrecognised becomes air
+const BLOCK_NAMES: &[(&str, u32, u32)] = &[
+    ("minecraft:air", 0, 0),
+    ("minecraft:stone", 1, 0),
+    ("minecraft:granite", 1, 1),
+    ("minecraft:diorite", 1, 3),
+    ("minecraft:andesite", 1, 5),
+    ("minecraft:grass_block", 2, 0),
+    ("minecraft:dirt", 3, 0),
+    ("minecraft:cobblestone", 4, 0),
+    ("minecraft:oak_planks", 5, 0),
+    ("minecraft:bedrock", 7, 0),
+    ("minecraft:water", 9, 0),
+    ("minecraft:lava", 11, 0),
+    ("minecraft:sand", 12, 0),
+    ("minecraft:gravel", 13, 0),
+    ("minecraft:gold_ore", 14, 0),
+    ("minecraft:iron_ore", 15, 0),
+    ("minecraft:coal_ore", 16, 0),
+    ("minecraft:oak_log", 17, 0),
+    ("minecraft:oak_leaves", 18, 0),
+    ("minecraft:glass", 20, 0),
+    ("minecraft:lapis_ore", 21, 0),
+    ("minecraft:sandstone", 24, 0),
+    ("minecraft:white_wool", 35, 0),
+    ("minecraft:gold_block", 41, 0),
+    ("minecraft:iron_block", 42, 0),
+    ("minecraft:stone_slab", 44, 0),
+    ("minecraft:brick_wall", 45, 0),
+    ("minecraft:tnt", 46, 0),
+    ("minecraft:bookshelf", 47, 0),
+    ("minecraft:mossy_cobblestone", 48, 0),
+    ("minecraft:obsidian", 49, 0),
+    ("minecraft:torch", 50, 0),
+    ("minecraft:diamond_ore", 56, 0),
+    ("minecraft:diamond_block", 57, 0),
+    ("minecraft:crafting_table", 58, 0),
+    ("minecraft:farmland", 60, 0),
+    ("minecraft:furnace", 61, 0),
+    ("minecraft:ladder", 65, 0),
+    ("minecraft:snow", 78, 0),
+    ("minecraft:ice", 79, 0),
+    ("minecraft:snow_block", 80, 0),
+    ("minecraft:clay", 82, 0),
+    ("minecraft:pumpkin", 86, 0),
+    ("minecraft:netherrack", 87, 0),
+    ("minecraft:soul_sand", 88, 0),
+    ("minecraft:glowstone", 89, 0),
+];
+
+fn legacy_block_for_name(name: &str) -> Block {
+    let base_name = name.split('[').next().unwrap_or(name);
+    BLOCK_NAMES
+        .iter()
+        .find(|(candidate, _, _)| *candidate == base_name)
+        .map(|(_, id, modifier)| Block::new(*id, *modifier))
+        .unwrap_or(Block::AIR)
+}
+
+fn name_for_legacy_block(block: Block) -> &'static str {
+    BLOCK_NAMES
+        .iter()
+        .find(|(_, id, modifier)| *id == block.id && *modifier == block.modifier)
+        .map(|(name, _, _)| *name)
+        .unwrap_or("minecraft:air")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_bit_packing_round_trip() {
+        let mut blocks = vec![Block::AIR; BLOCKS_PER_SECTION];
+        for (i, block) in blocks.iter_mut().enumerate() {
+            *block = match i % 3 {
+                0 => Block::new(1, 0), // minecraft:stone
+                1 => Block::new(2, 0), // minecraft:grass_block
+                _ => Block::AIR,
+            };
+        }
+
+        let section = build_section_nbt(0, &blocks);
+        let root = Value::Compound(vec![(
+            "Level".to_string(),
+            Value::Compound(vec![("Sections".to_string(), Value::List(vec![section]))]),
+        )]);
+
+        let mut decoded = Vec::new();
+        read_chunk_blocks(
+            &root,
+            0,
+            0,
+            Coordinate::new(0, 0, 0),
+            Coordinate::new(CHUNK_SIDE, SECTION_HEIGHT, CHUNK_SIDE),
+            &mut decoded,
+        )
+        .expect("hand-built section should decode");
+
+        assert_eq!(decoded.len(), BLOCKS_PER_SECTION);
+        for (coord, block) in decoded {
+            let index = (coord.y * CHUNK_SIDE * CHUNK_SIDE + coord.z * CHUNK_SIDE + coord.x) as usize;
+            assert_eq!(block, blocks[index]);
+        }
+    }
+
+    fn single_entry_palette(name: &str) -> Value {
+        Value::List(vec![Value::Compound(vec![("Name".to_string(), Value::String(name.to_string()))])])
+    }
+
+    fn section_with_sections(section: Value) -> Value {
+        Value::Compound(vec![(
+            "Level".to_string(),
+            Value::Compound(vec![("Sections".to_string(), Value::List(vec![section]))]),
+        )])
+    }
+
+    #[test]
+    fn uniform_section_without_block_states_fills_from_single_entry_palette() {
+        // Vanilla omits `BlockStates` entirely for a uniform section
+        let section = Value::Compound(vec![
+            ("Y".to_string(), Value::Byte(0)),
+            ("Palette".to_string(), single_entry_palette("minecraft:stone")),
+        ]);
+        let root = section_with_sections(section);
+
+        let mut decoded = Vec::new();
+        read_chunk_blocks(
+            &root,
+            0,
+            0,
+            Coordinate::new(0, 0, 0),
+            Coordinate::new(CHUNK_SIDE, SECTION_HEIGHT, CHUNK_SIDE),
+            &mut decoded,
+        )
+        .expect("uniform section should decode");
+
+        assert_eq!(decoded.len(), BLOCKS_PER_SECTION);
+        assert!(decoded.iter().all(|(_, block)| *block == Block::new(1, 0)));
+    }
+
+    #[test]
+    fn uniform_section_with_empty_block_states_fills_from_single_entry_palette() {
+        // Some writers instead emit an empty `BlockStates` array for a uniform section
+        let section = Value::Compound(vec![
+            ("Y".to_string(), Value::Byte(0)),
+            ("Palette".to_string(), single_entry_palette("minecraft:air")),
+            ("BlockStates".to_string(), Value::LongArray(Vec::new())),
+        ]);
+        let root = section_with_sections(section);
+
+        let mut decoded = Vec::new();
+        read_chunk_blocks(
+            &root,
+            0,
+            0,
+            Coordinate::new(0, 0, 0),
+            Coordinate::new(CHUNK_SIDE, SECTION_HEIGHT, CHUNK_SIDE),
+            &mut decoded,
+        )
+        .expect("uniform section should decode");
+
+        assert_eq!(decoded.len(), BLOCKS_PER_SECTION);
+        assert!(decoded.iter().all(|(_, block)| *block == Block::AIR));
+    }
+}
@@ -1,7 +1,8 @@
 use std::{error, fmt};
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 use mcrs::Coordinate;
+use mcutils::Codec;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
@@ -38,15 +39,100 @@ pub enum Command {
         /// Second corner of 3D block region
         #[arg(value_parser = parse_coordinate)]
         bound: Coordinate,
+        /// Stream compression to apply to the saved file
+        #[arg(long, value_enum, default_value_t = Compression::None)]
+        compress: Compression,
     },
 
     /// Load a 3D block region from a file
     ///
-    /// Always loads region at same position it was saved
+    /// By default, loads the region back at the same position it was saved;
+    /// use `--to`/`--rotate`/`--mirror` to paste it as a reusable prefab
     Load {
         /// Name of binary file to load from
         filename: String,
+        /// Paste the region at this position instead of its saved origin
+        #[arg(long, value_parser = parse_coordinate)]
+        to: Option<Coordinate>,
+        /// Rotate the region clockwise (viewed from above) before placing it
+        #[arg(long, value_enum)]
+        rotate: Option<Rotation>,
+        /// Mirror the region across the given horizontal axis before placing it
+        #[arg(long, value_enum)]
+        mirror: Option<MirrorAxis>,
     },
+
+    /// Import a 3D block region from a vanilla Minecraft Anvil (.mca) region file
+    ///
+    /// Order of bounding coordinates do not matter; they will be normalized
+    Import {
+        /// Name of `.mca` region file to import from
+        filename: String,
+        /// First corner of 3D block region
+        #[arg(value_parser = parse_coordinate)]
+        origin: Coordinate,
+        /// Second corner of 3D block region
+        #[arg(value_parser = parse_coordinate)]
+        bound: Coordinate,
+    },
+
+    /// Export a 3D block region to a vanilla Minecraft Anvil (.mca) region file
+    ///
+    /// Order of bounding coordinates do not matter; they will be normalized
+    Export {
+        /// Name of `.mca` region file to export to
+        filename: String,
+        /// First corner of 3D block region
+        #[arg(value_parser = parse_coordinate)]
+        origin: Coordinate,
+        /// Second corner of 3D block region
+        #[arg(value_parser = parse_coordinate)]
+        bound: Coordinate,
+    },
+
+    /// Undo the last `clear` or `load` operation
+    ///
+    /// Replays the most recent automatic undo snapshot and then discards it,
+    /// so running `undo` repeatedly walks further back in history
+    Undo,
+}
+
+/// Stream compression codec to use when saving, selected via `--compress`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Compression {
+    None,
+    Zstd,
+    Zlib,
+}
+
+impl From<Compression> for Codec {
+    fn from(compression: Compression) -> Self {
+        match compression {
+            Compression::None => Codec::None,
+            Compression::Zstd => Codec::Zstd,
+            Compression::Zlib => Codec::Zlib,
+        }
+    }
+}
+
+/// Clockwise rotation (viewed from above) to apply when loading, selected via `--rotate`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Rotation {
+    #[value(name = "0")]
+    Deg0,
+    #[value(name = "90")]
+    Deg90,
+    #[value(name = "180")]
+    Deg180,
+    #[value(name = "270")]
+    Deg270,
+}
+
+/// Horizontal axis to mirror across when loading, selected via `--mirror`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum MirrorAxis {
+    X,
+    Z,
 }
 
 #[derive(Debug)]
@@ -4,59 +4,232 @@ use anyhow::{Result, bail};
 use mcrs::chunk::ChunkStream;
 use mcrs::{Block, Coordinate, Size};
 
+pub mod anvil;
+mod nbt;
+
 const MAGIC_NUMBER: u16 = 0xa3f9;
-const VERSION: u16 = 0x01_00;
 
-pub fn write_data(file: &mut impl Write, chunk: &mut ChunkStream<'_>) -> Result<()> {
+const VERSION_FLAT: u16 = 0x01_00;
+const VERSION_RLE: u16 = 0x02_00;
+const VERSION_CHECKSUM: u16 = 0x03_00;
+const VERSION_COMPRESSED: u16 = 0x04_00;
+const VERSION: u16 = VERSION_COMPRESSED;
+
+/// Stream compression applied to everything in the file after the codec byte
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codec {
+    None,
+    Zstd,
+    Zlib,
+}
+
+impl Codec {
+    fn to_byte(self) -> u8 {
+        match self {
+            Codec::None => 0,
+            Codec::Zstd => 1,
+            Codec::Zlib => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(Codec::None),
+            1 => Ok(Codec::Zstd),
+            2 => Ok(Codec::Zlib),
+            _ => bail!("Unknown compression codec in file"),
+        }
+    }
+
+    /// Compresses `data` into `writer`, explicitly finalizing the stream so a
+    /// late compression error is propagated instead of swallowed by `Drop`
+    fn compress_into(self, writer: &mut impl Write, data: &[u8]) -> Result<()> {
+        match self {
+            Codec::None => writer.write_all(data)?,
+            Codec::Zstd => {
+                let mut encoder = zstd::Encoder::new(writer, 0)?;
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            }
+            Codec::Zlib => {
+                let mut encoder = flate2::write::ZlibEncoder::new(writer, flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn decoder<'a, R: Read + 'a>(self, reader: R) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self {
+            Codec::None => Box::new(reader),
+            Codec::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            Codec::Zlib => Box::new(flate2::read::ZlibDecoder::new(reader)),
+        })
+    }
+}
+
+pub fn write_data(file: &mut impl Write, chunk: &mut ChunkStream<'_>, codec: Codec) -> Result<()> {
+    let origin = chunk.origin();
+    let size = chunk.size();
+
     file.write_all(&MAGIC_NUMBER.to_le_bytes())?;
     file.write_all(&VERSION.to_le_bytes())?;
+    file.write_all(&[codec.to_byte()])?;
 
-    file.write_all(&chunk.origin().x.to_le_bytes())?;
-    file.write_all(&chunk.origin().y.to_le_bytes())?;
-    file.write_all(&chunk.origin().z.to_le_bytes())?;
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&origin.x.to_le_bytes());
+    hasher.update(&origin.y.to_le_bytes());
+    hasher.update(&origin.z.to_le_bytes());
+    hasher.update(&size.x.to_le_bytes());
+    hasher.update(&size.y.to_le_bytes());
+    hasher.update(&size.z.to_le_bytes());
 
-    file.write_all(&chunk.size().x.to_le_bytes())?;
-    file.write_all(&chunk.size().y.to_le_bytes())?;
-    file.write_all(&chunk.size().z.to_le_bytes())?;
+    let mut body = Vec::new();
+    let mut block_count: u32 = 0;
+    let mut run: Option<(Block, u32)> = None;
 
     while let Some(item) = chunk.next()? {
-        file.write_all(&item.block().id.to_le_bytes())?;
-        file.write_all(&item.block().modifier.to_le_bytes())?;
+        let block = *item.block();
+        block_count += 1;
+        match &mut run {
+            Some((current, count)) if *current == block => {
+                *count += 1;
+            }
+            Some((current, count)) => {
+                write_run(&mut body, &mut hasher, *current, *count)?;
+                run = Some((block, 1));
+            }
+            None => {
+                run = Some((block, 1));
+            }
+        }
+    }
+
+    if let Some((block, count)) = run {
+        write_run(&mut body, &mut hasher, block, count)?;
     }
 
+    let checksum = hasher.finalize();
+
+    let mut section = Vec::with_capacity(32 + body.len());
+    section.extend_from_slice(&checksum.to_le_bytes());
+    section.extend_from_slice(&block_count.to_le_bytes());
+
+    section.extend_from_slice(&origin.x.to_le_bytes());
+    section.extend_from_slice(&origin.y.to_le_bytes());
+    section.extend_from_slice(&origin.z.to_le_bytes());
+
+    section.extend_from_slice(&size.x.to_le_bytes());
+    section.extend_from_slice(&size.y.to_le_bytes());
+    section.extend_from_slice(&size.z.to_le_bytes());
+
+    section.extend_from_slice(&body);
+
+    codec.compress_into(file, &section)?;
+
     Ok(())
 }
 
-pub fn read_data<R: Read>(file: &mut R) -> Result<BlockReader<R>> {
-    check_data_metadata(file)?;
+fn write_run(body: &mut Vec<u8>, hasher: &mut crc32fast::Hasher, block: Block, count: u32) -> Result<()> {
+    let mut buf = [0u8; 12];
+    buf[0..4].copy_from_slice(&count.to_le_bytes());
+    buf[4..8].copy_from_slice(&block.id.to_le_bytes());
+    buf[8..12].copy_from_slice(&block.modifier.to_le_bytes());
+    hasher.update(&buf);
+    body.write_all(&buf)?;
+    Ok(())
+}
+
+pub fn read_data<'a, R: Read + 'a>(file: &'a mut R) -> Result<BlockReader<'a>> {
+    let version = check_data_metadata(file)?;
+
+    let codec = if version >= VERSION_COMPRESSED {
+        Codec::from_byte(read_u8(file)?)?
+    } else {
+        Codec::None
+    };
+    let mut reader = codec.decoder(file)?;
 
-    let x = read_i32(file)?;
-    let y = read_i32(file)?;
-    let z = read_i32(file)?;
+    let (expected_checksum, block_count) = if version >= VERSION_CHECKSUM {
+        let checksum = read_u32(&mut reader)?;
+        let count = read_u32(&mut reader)?;
+        (Some(checksum), Some(count))
+    } else {
+        (None, None)
+    };
+
+    let x = read_i32(&mut reader)?;
+    let y = read_i32(&mut reader)?;
+    let z = read_i32(&mut reader)?;
     let origin = Coordinate::new(x, y, z);
 
-    let x = read_u32(file)?;
-    let y = read_u32(file)?;
-    let z = read_u32(file)?;
+    let x = read_u32(&mut reader)?;
+    let y = read_u32(&mut reader)?;
+    let z = read_u32(&mut reader)?;
     let size = Size::new(x, y, z);
 
-    Ok(BlockReader::new(file, origin, size))
+    let hasher = expected_checksum.map(|_| {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&origin.x.to_le_bytes());
+        hasher.update(&origin.y.to_le_bytes());
+        hasher.update(&origin.z.to_le_bytes());
+        hasher.update(&size.x.to_le_bytes());
+        hasher.update(&size.y.to_le_bytes());
+        hasher.update(&size.z.to_le_bytes());
+        hasher
+    });
+
+    Ok(BlockReader::new(
+        reader,
+        origin,
+        size,
+        version,
+        hasher,
+        expected_checksum,
+        block_count,
+    ))
 }
 
-pub struct BlockReader<'a, R> {
-    reader: &'a mut R,
+pub struct BlockReader<'a> {
+    reader: Box<dyn Read + 'a>,
     index: u32,
     origin: Coordinate,
     size: Size,
+    version: u16,
+    /// Remaining repetitions of `run_block` yet to be yielded (RLE format only)
+    remaining: u32,
+    run_block: Block,
+    /// Accumulates over every record read so far, when the file declares a checksum
+    hasher: Option<crc32fast::Hasher>,
+    expected_checksum: Option<u32>,
+    block_count: Option<u32>,
+    checksum_checked: bool,
 }
 
-impl<'a, R> BlockReader<'a, R> {
-    fn new(reader: &'a mut R, origin: Coordinate, size: Size) -> Self {
+impl<'a> BlockReader<'a> {
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        reader: Box<dyn Read + 'a>,
+        origin: Coordinate,
+        size: Size,
+        version: u16,
+        hasher: Option<crc32fast::Hasher>,
+        expected_checksum: Option<u32>,
+        block_count: Option<u32>,
+    ) -> Self {
         Self {
             reader,
             index: 0,
             origin,
             size,
+            version,
+            remaining: 0,
+            run_block: Block::AIR,
+            hasher,
+            expected_checksum,
+            block_count,
+            checksum_checked: false,
         }
     }
 
@@ -71,20 +244,34 @@ impl<'a, R> BlockReader<'a, R> {
     }
 }
 
-impl<'a, R: Read> Iterator for &mut BlockReader<'a, R> {
+impl<'a> Iterator for &mut BlockReader<'a> {
     type Item = Result<(Coordinate, Block)>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let id = match try_read_u32(self.reader) {
-            Ok(Some(id)) => id,
-            Ok(None) => return None,
-            Err(error) => return Some(Err(error.into())),
-        };
-        let modifier = match read_u32(self.reader) {
-            Ok(modifier) => modifier,
-            Err(error) => return Some(Err(error.into())),
+        if let Some(count) = self.block_count {
+            if self.index >= count {
+                return self.verify_checksum();
+            }
+        }
+
+        let block = if self.version >= VERSION_RLE {
+            match self.next_rle_block() {
+                Ok(Some(block)) => block,
+                Ok(None) => return None,
+                Err(error) => return Some(Err(error)),
+            }
+        } else {
+            let id = match try_read_u32(&mut self.reader) {
+                Ok(Some(id)) => id,
+                Ok(None) => return None,
+                Err(error) => return Some(Err(error.into())),
+            };
+            let modifier = match read_u32(&mut self.reader) {
+                Ok(modifier) => modifier,
+                Err(error) => return Some(Err(error.into())),
+            };
+            Block::new(id, modifier)
         };
-        let block = Block::new(id, modifier);
 
         let coordinate = self.origin + self.size.index_to_offset(self.index as usize);
 
@@ -93,18 +280,76 @@ impl<'a, R: Read> Iterator for &mut BlockReader<'a, R> {
     }
 }
 
-fn check_data_metadata(file: &mut impl Read) -> Result<()> {
+impl<'a> BlockReader<'a> {
+    fn next_rle_block(&mut self) -> Result<Option<Block>> {
+        if self.remaining == 0 {
+            let Some(count) = try_read_u32(&mut self.reader)? else {
+                return Ok(None);
+            };
+            if count == 0 || count as usize > self.size.volume() {
+                bail!("Truncated data in file");
+            }
+            let id = read_u32_required(&mut self.reader)?;
+            let modifier = read_u32_required(&mut self.reader)?;
+
+            if let Some(hasher) = &mut self.hasher {
+                hasher.update(&count.to_le_bytes());
+                hasher.update(&id.to_le_bytes());
+                hasher.update(&modifier.to_le_bytes());
+            }
+
+            self.remaining = count;
+            self.run_block = Block::new(id, modifier);
+        }
+
+        self.remaining -= 1;
+        Ok(Some(self.run_block))
+    }
+
+    /// Once the declared block count has been reached, finalize the running
+    /// hash and compare it against the checksum recorded in the header
+    fn verify_checksum(&mut self) -> Option<Result<(Coordinate, Block)>> {
+        if self.checksum_checked {
+            return None;
+        }
+        self.checksum_checked = true;
+
+        if let (Some(hasher), Some(expected)) = (self.hasher.take(), self.expected_checksum) {
+            let actual = hasher.finalize();
+            if actual != expected {
+                return Some(Err(anyhow::anyhow!("checksum mismatch")));
+            }
+        }
+        None
+    }
+}
+
+/// Reads and validates the magic number and version header, returning the
+/// version so the caller can pick the matching block encoding
+fn check_data_metadata(file: &mut impl Read) -> Result<u16> {
     let magic_number = read_u16(file)?;
     if magic_number != MAGIC_NUMBER {
         bail!("Invalid file format (signature does not match)");
     }
     let version = read_u16(file)?;
-    if version < VERSION {
-        bail!("Outdated file format (try using an older version of mcutils)");
-    } else if version > VERSION {
-        bail!("Outdated program (try updating mcutils)");
+    if version != VERSION_FLAT
+        && version != VERSION_RLE
+        && version != VERSION_CHECKSUM
+        && version != VERSION_COMPRESSED
+    {
+        if version < VERSION {
+            bail!("Outdated file format (try using an older version of mcutils)");
+        } else {
+            bail!("Outdated program (try updating mcutils)");
+        }
     }
-    Ok(())
+    Ok(version)
+}
+
+fn read_u8(file: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    file.read_exact(&mut buf)?;
+    Ok(buf[0])
 }
 
 fn read_u16(file: &mut impl Read) -> io::Result<u16> {
@@ -125,6 +370,19 @@ fn read_u32(file: &mut impl Read) -> io::Result<u32> {
     Ok(u32::from_le_bytes(buf))
 }
 
+/// Reads a `u32` expected to be present, bailing with the same "truncated"
+/// message as `try_read_u32` if the file ends partway through it instead of
+/// surfacing a raw `UnexpectedEof` io error
+fn read_u32_required(file: &mut impl Read) -> Result<u32> {
+    match read_u32(file) {
+        Ok(value) => Ok(value),
+        Err(error) if error.kind() == io::ErrorKind::UnexpectedEof => {
+            bail!("Truncated data in file")
+        }
+        Err(error) => Err(error.into()),
+    }
+}
+
 fn try_read_u32(file: &mut impl Read) -> Result<Option<u32>> {
     let mut buf = [0u8; 4];
     let bytes_read = file.read(&mut buf)?;
@@ -136,3 +394,87 @@ fn try_read_u32(file: &mut impl Read) -> Result<Option<u32>> {
     }
     Ok(Some(u32::from_le_bytes(buf)))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use mcrs::{Block, Coordinate, Size};
+
+    use super::{Codec, MAGIC_NUMBER, VERSION, read_data};
+
+    /// Hand-assembles an uncompressed, checksummed, RLE-encoded file in the
+    /// same layout `write_data` produces, so the read side can be exercised
+    /// without a live `ChunkStream`
+    fn build_file(corrupt_checksum: bool) -> (Vec<u8>, u32) {
+        let origin = Coordinate::new(1, 2, 3);
+        let size = Size::new(2, 1, 2);
+        let runs = [(3u32, 5u32, 0u32), (1u32, 7u32, 2u32)];
+        let block_count: u32 = runs.iter().map(|(count, _, _)| count).sum();
+
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&origin.x.to_le_bytes());
+        hasher.update(&origin.y.to_le_bytes());
+        hasher.update(&origin.z.to_le_bytes());
+        hasher.update(&size.x.to_le_bytes());
+        hasher.update(&size.y.to_le_bytes());
+        hasher.update(&size.z.to_le_bytes());
+
+        let mut body = Vec::new();
+        for (count, id, modifier) in runs {
+            let mut buf = [0u8; 12];
+            buf[0..4].copy_from_slice(&count.to_le_bytes());
+            buf[4..8].copy_from_slice(&id.to_le_bytes());
+            buf[8..12].copy_from_slice(&modifier.to_le_bytes());
+            hasher.update(&buf);
+            body.extend_from_slice(&buf);
+        }
+
+        let mut checksum = hasher.finalize();
+        if corrupt_checksum {
+            checksum ^= 1;
+        }
+
+        let mut file = Vec::new();
+        file.extend_from_slice(&MAGIC_NUMBER.to_le_bytes());
+        file.extend_from_slice(&VERSION.to_le_bytes());
+        file.push(Codec::None.to_byte());
+        file.extend_from_slice(&checksum.to_le_bytes());
+        file.extend_from_slice(&block_count.to_le_bytes());
+        file.extend_from_slice(&origin.x.to_le_bytes());
+        file.extend_from_slice(&origin.y.to_le_bytes());
+        file.extend_from_slice(&origin.z.to_le_bytes());
+        file.extend_from_slice(&size.x.to_le_bytes());
+        file.extend_from_slice(&size.y.to_le_bytes());
+        file.extend_from_slice(&size.z.to_le_bytes());
+        file.extend_from_slice(&body);
+
+        (file, block_count)
+    }
+
+    #[test]
+    fn rle_checksum_round_trip() {
+        let (file, block_count) = build_file(false);
+        let mut reader = Cursor::new(file);
+        let mut entries = read_data(&mut reader).expect("header should parse");
+
+        let blocks: Vec<_> = (&mut entries).map(|entry| entry.expect("run should decode")).collect();
+
+        assert_eq!(blocks.len(), block_count as usize);
+        assert_eq!(blocks[0].1, Block::new(5, 0));
+        assert_eq!(blocks[2].1, Block::new(5, 0));
+        assert_eq!(blocks[3].1, Block::new(7, 2));
+    }
+
+    #[test]
+    fn rejects_corrupt_checksum() {
+        let (file, block_count) = build_file(true);
+        let mut reader = Cursor::new(file);
+        let mut entries = read_data(&mut reader).expect("header should parse");
+
+        let results: Vec<_> = (&mut entries).collect();
+
+        assert!(results[..block_count as usize].iter().all(|entry| entry.is_ok()));
+        assert!(results.last().expect("checksum check should yield an item").is_err());
+    }
+}
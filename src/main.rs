@@ -1,15 +1,18 @@
 mod args;
+mod undo;
 
 use std::fs;
 use std::io;
+use std::path::Path;
 
 use anyhow::Result;
 use clap::Parser;
 use mcrs::Block;
 use mcrs::Connection;
 
-use crate::args::Command;
-use mcutils::{read_data, write_data};
+use crate::args::{Command, MirrorAxis, Rotation};
+use mcrs::Size;
+use mcutils::{anvil, read_data, write_data};
 
 fn main() -> Result<()> {
     let args = args::Args::parse();
@@ -18,6 +21,8 @@ fn main() -> Result<()> {
 
     match args.command {
         Command::Clear { origin, bound } => {
+            undo::snapshot(&mut mc, origin, bound)?;
+
             let chunk = mc.get_blocks(origin, bound)?;
             let size = origin.size_between(bound);
 
@@ -39,6 +44,7 @@ fn main() -> Result<()> {
             filename,
             origin,
             bound,
+            compress,
         } => {
             let file = fs::OpenOptions::new()
                 .create(true)
@@ -48,7 +54,7 @@ fn main() -> Result<()> {
             let mut writer = io::BufWriter::new(file);
 
             let mut chunk = mc.get_blocks_stream(origin, bound)?;
-            write_data(&mut writer, &mut chunk)?;
+            write_data(&mut writer, &mut chunk, compress.into())?;
 
             println!(
                 "Successfully saved {:?} chunk at {}.",
@@ -57,16 +63,43 @@ fn main() -> Result<()> {
             );
         }
 
-        Command::Load { filename } => {
+        Command::Load {
+            filename,
+            to,
+            rotate,
+            mirror,
+        } => {
             let file = fs::OpenOptions::new().read(true).open(filename)?;
             let mut reader = io::BufReader::new(file);
 
             let mut entries = read_data(&mut reader)?;
 
-            let chunk = mc.get_blocks(entries.origin(), entries.bound())?;
+            let size = entries.size();
+            let rotate = rotate.unwrap_or(Rotation::Deg0);
+            let target_origin = to.unwrap_or(entries.origin());
+            let target_size = rotated_size(size, rotate);
+            let target_bound = target_origin + target_size;
 
+            undo::snapshot(&mut mc, target_origin, target_bound)?;
+
+            let chunk = mc.get_blocks(target_origin, target_bound)?;
+
+            // Fully drain and validate the file (this is what runs the checksum
+            // check) before writing anything, so a corrupt file is rejected
+            // instead of being partially applied to the live world
+            let mut index = 0;
+            let mut blocks = Vec::with_capacity(size.volume());
             for entry in &mut entries {
-                let (coord, block) = entry?;
+                let (_coord, block) = entry?;
+                let offset = size.index_to_offset(index);
+                index += 1;
+                blocks.push((offset, block));
+            }
+
+            for (offset, block) in blocks {
+                let offset = transform_offset(offset, size, rotate, mirror);
+                let coord = target_origin + offset;
+
                 let current_block = chunk
                     .get_worldspace(coord)
                     .expect("Chunk should contain coordinate");
@@ -77,11 +110,95 @@ fn main() -> Result<()> {
 
             println!(
                 "Successfully loaded {:?} chunk at {}.",
-                entries.size(),
-                entries.origin()
+                target_size, target_origin
             );
         }
+
+        Command::Import {
+            filename,
+            origin,
+            bound,
+        } => {
+            let blocks = anvil::import_region(Path::new(&filename), origin, bound)?;
+            let chunk = mc.get_blocks(origin, bound)?;
+
+            for (coord, block) in &blocks {
+                let current_block = chunk
+                    .get_worldspace(*coord)
+                    .expect("Chunk should contain coordinate");
+                if *block != current_block {
+                    mc.set_block(*coord, *block)?;
+                }
+            }
+
+            println!(
+                "Successfully imported {} blocks from {} at {}.",
+                blocks.len(),
+                filename,
+                origin
+            );
+        }
+
+        Command::Export {
+            filename,
+            origin,
+            bound,
+        } => {
+            let size = origin.size_between(bound);
+            let mut chunk = mc.get_blocks_stream(origin, bound)?;
+            let mut index = 0;
+
+            anvil::export_region(Path::new(&filename), origin, bound, || {
+                let Some(item) = chunk.next()? else {
+                    return Ok(None);
+                };
+                let coord = origin + size.index_to_offset(index);
+                index += 1;
+                Ok(Some((coord, *item.block())))
+            })?;
+
+            println!("Successfully exported {:?} chunk at {} to {}.", size, origin, filename);
+        }
+
+        Command::Undo => match undo::restore_latest(&mut mc)? {
+            Some((size, origin)) => {
+                println!("Successfully undid last operation, restoring {:?} chunk at {}.", size, origin);
+            }
+            None => {
+                println!("No undo snapshots available.");
+            }
+        },
     }
 
     Ok(())
 }
+
+/// Size of the region once rotated; 90/270 degree rotations swap the X/Z extents
+fn rotated_size(size: Size, rotate: Rotation) -> Size {
+    match rotate {
+        Rotation::Deg0 | Rotation::Deg180 => size,
+        Rotation::Deg90 | Rotation::Deg270 => Size::new(size.z, size.y, size.x),
+    }
+}
+
+/// Remaps a block's offset within the saved region's bounding box, mirroring
+/// then rotating it around the vertical axis
+fn transform_offset(offset: Size, size: Size, rotate: Rotation, mirror: Option<MirrorAxis>) -> Size {
+    let mut x = offset.x;
+    let mut z = offset.z;
+
+    match mirror {
+        Some(MirrorAxis::X) => x = size.x - 1 - x,
+        Some(MirrorAxis::Z) => z = size.z - 1 - z,
+        None => {}
+    }
+
+    let (x, z) = match rotate {
+        Rotation::Deg0 => (x, z),
+        Rotation::Deg90 => (size.z - 1 - z, x),
+        Rotation::Deg180 => (size.x - 1 - x, size.z - 1 - z),
+        Rotation::Deg270 => (z, size.x - 1 - x),
+    };
+
+    Size::new(x, offset.y, z)
+}
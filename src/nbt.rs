@@ -0,0 +1,280 @@
+//! Minimal big-endian NBT reader/writer, just enough of the spec to walk an
+//! Anvil chunk's `Level`/`Sections` tree (see [`crate::anvil`])
+
+use std::io::{self, Read, Write};
+
+use anyhow::{Result, bail};
+
+/// Upper bound on any single NBT array/list length; well above anything a
+/// real chunk needs, just enough to stop a corrupt length from demanding an
+/// absurd allocation or looping for a very long time
+const MAX_SEQUENCE_LEN: i32 = 1 << 24;
+
+#[derive(Debug, Clone)]
+pub enum Value {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Value>),
+    Compound(Vec<(String, Value)>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Value {
+    pub fn as_compound(&self) -> Option<&[(String, Value)]> {
+        match self {
+            Value::Compound(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_byte(&self) -> Option<i8> {
+        match self {
+            Value::Byte(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&[i64]> {
+        match self {
+            Value::LongArray(longs) => Some(longs),
+            _ => None,
+        }
+    }
+
+    pub fn get<'a>(&'a self, name: &str) -> Option<&'a Value> {
+        self.as_compound()?
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    fn tag_id(&self) -> u8 {
+        match self {
+            Value::Byte(_) => 1,
+            Value::Short(_) => 2,
+            Value::Int(_) => 3,
+            Value::Long(_) => 4,
+            Value::Float(_) => 5,
+            Value::Double(_) => 6,
+            Value::ByteArray(_) => 7,
+            Value::String(_) => 8,
+            Value::List(_) => 9,
+            Value::Compound(_) => 10,
+            Value::IntArray(_) => 11,
+            Value::LongArray(_) => 12,
+        }
+    }
+}
+
+/// Parses a whole NBT document (an unnamed root compound) from `reader`
+pub fn read_root(reader: &mut impl Read) -> Result<Value> {
+    let tag_id = read_u8(reader)?;
+    if tag_id != 10 {
+        bail!("Expected a root NBT compound tag");
+    }
+    let _name = read_string(reader)?;
+    read_payload(reader, tag_id)
+}
+
+fn read_payload(reader: &mut impl Read, tag_id: u8) -> Result<Value> {
+    Ok(match tag_id {
+        1 => Value::Byte(read_i8(reader)?),
+        2 => Value::Short(read_i16(reader)?),
+        3 => Value::Int(read_i32(reader)?),
+        4 => Value::Long(read_i64(reader)?),
+        5 => Value::Float(f32::from_bits(read_u32(reader)?)),
+        6 => Value::Double(f64::from_bits(read_u64(reader)?)),
+        7 => {
+            let len = read_sequence_len(reader)?;
+            let mut bytes = vec![0u8; len];
+            reader.read_exact(&mut bytes)?;
+            Value::ByteArray(bytes.into_iter().map(|b| b as i8).collect())
+        }
+        8 => Value::String(read_string(reader)?),
+        9 => {
+            let item_tag = read_u8(reader)?;
+            let len = read_sequence_len(reader)?;
+            let mut items = Vec::new();
+            for _ in 0..len {
+                if item_tag == 0 {
+                    continue;
+                }
+                items.push(read_payload(reader, item_tag)?);
+            }
+            Value::List(items)
+        }
+        10 => {
+            let mut entries = Vec::new();
+            loop {
+                let child_tag = read_u8(reader)?;
+                if child_tag == 0 {
+                    break;
+                }
+                let name = read_string(reader)?;
+                entries.push((name, read_payload(reader, child_tag)?));
+            }
+            Value::Compound(entries)
+        }
+        11 => {
+            let len = read_sequence_len(reader)?;
+            let mut ints = Vec::with_capacity(len);
+            for _ in 0..len {
+                ints.push(read_i32(reader)?);
+            }
+            Value::IntArray(ints)
+        }
+        12 => {
+            let len = read_sequence_len(reader)?;
+            let mut longs = Vec::with_capacity(len);
+            for _ in 0..len {
+                longs.push(read_i64(reader)?);
+            }
+            Value::LongArray(longs)
+        }
+        _ => bail!("Unknown NBT tag id {tag_id}"),
+    })
+}
+
+/// Writes `value` as an unnamed root NBT compound to `writer`
+pub fn write_root(writer: &mut impl Write, value: &Value) -> Result<()> {
+    writer.write_all(&[value.tag_id()])?;
+    write_string(writer, "")?;
+    write_payload(writer, value)
+}
+
+fn write_payload(writer: &mut impl Write, value: &Value) -> Result<()> {
+    match value {
+        Value::Byte(b) => writer.write_all(&b.to_be_bytes())?,
+        Value::Short(s) => writer.write_all(&s.to_be_bytes())?,
+        Value::Int(i) => writer.write_all(&i.to_be_bytes())?,
+        Value::Long(l) => writer.write_all(&l.to_be_bytes())?,
+        Value::Float(f) => writer.write_all(&f.to_bits().to_be_bytes())?,
+        Value::Double(d) => writer.write_all(&d.to_bits().to_be_bytes())?,
+        Value::ByteArray(bytes) => {
+            writer.write_all(&(bytes.len() as i32).to_be_bytes())?;
+            for byte in bytes {
+                writer.write_all(&byte.to_be_bytes())?;
+            }
+        }
+        Value::String(s) => write_string(writer, s)?,
+        Value::List(items) => {
+            let item_tag = items.first().map_or(0, Value::tag_id);
+            writer.write_all(&[item_tag])?;
+            writer.write_all(&(items.len() as i32).to_be_bytes())?;
+            for item in items {
+                write_payload(writer, item)?;
+            }
+        }
+        Value::Compound(entries) => {
+            for (name, entry) in entries {
+                writer.write_all(&[entry.tag_id()])?;
+                write_string(writer, name)?;
+                write_payload(writer, entry)?;
+            }
+            writer.write_all(&[0u8])?;
+        }
+        Value::IntArray(ints) => {
+            writer.write_all(&(ints.len() as i32).to_be_bytes())?;
+            for int in ints {
+                writer.write_all(&int.to_be_bytes())?;
+            }
+        }
+        Value::LongArray(longs) => {
+            writer.write_all(&(longs.len() as i32).to_be_bytes())?;
+            for long in longs {
+                writer.write_all(&long.to_be_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads a big-endian `i32` array/list length, rejecting negative or
+/// unreasonably large values before the caller allocates or loops on it
+fn read_sequence_len(reader: &mut impl Read) -> Result<usize> {
+    let len = read_i32(reader)?;
+    if !(0..=MAX_SEQUENCE_LEN).contains(&len) {
+        bail!("Invalid NBT array/list length {len}");
+    }
+    Ok(len as usize)
+}
+
+fn read_string(reader: &mut impl Read) -> Result<String> {
+    let len = read_u16(reader)? as usize;
+    let mut bytes = vec![0u8; len];
+    reader.read_exact(&mut bytes)?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn write_string(writer: &mut impl Write, s: &str) -> Result<()> {
+    writer.write_all(&(s.len() as u16).to_be_bytes())?;
+    writer.write_all(s.as_bytes())?;
+    Ok(())
+}
+
+fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    reader.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_i8(reader: &mut impl Read) -> io::Result<i8> {
+    Ok(read_u8(reader)? as i8)
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_i16(reader: &mut impl Read) -> io::Result<i16> {
+    let mut buf = [0u8; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(i16::from_be_bytes(buf))
+}
+
+fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(i32::from_be_bytes(buf))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+fn read_i64(reader: &mut impl Read) -> io::Result<i64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(i64::from_be_bytes(buf))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0u8; 8];
+    reader.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
@@ -0,0 +1,99 @@
+//! Automatic undo snapshots, taken before destructive operations so a `clear`
+//! or `load` on a live server can be rolled back with `mcutils undo`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use mcrs::{Connection, Coordinate, Size};
+use mcutils::{Codec, read_data, write_data};
+
+const UNDO_DIR: &str = ".mcutils-undo";
+/// Number of past snapshots to keep; the oldest is deleted once this is exceeded
+const MAX_SNAPSHOTS: usize = 10;
+
+/// Captures the current state of `origin..bound` into a new rotating undo
+/// snapshot, reusing the same file format `save`/`load` use
+pub fn snapshot(mc: &mut Connection, origin: Coordinate, bound: Coordinate) -> Result<()> {
+    fs::create_dir_all(UNDO_DIR)?;
+
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let path = Path::new(UNDO_DIR).join(format!("{millis:020}.dat"));
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(true)
+        .open(&path)?;
+    let mut writer = io::BufWriter::new(file);
+
+    let mut chunk = mc.get_blocks_stream(origin, bound)?;
+    write_data(&mut writer, &mut chunk, Codec::None)?;
+
+    prune_old_snapshots()?;
+    Ok(())
+}
+
+/// Replays the most recent undo snapshot using the same "set only changed
+/// blocks" diff as `load`, then discards it. Returns `None` if there is
+/// nothing to undo.
+pub fn restore_latest(mc: &mut Connection) -> Result<Option<(Size, Coordinate)>> {
+    let mut snapshots = list_snapshots()?;
+    snapshots.sort();
+    let Some(path) = snapshots.pop() else {
+        return Ok(None);
+    };
+
+    let file = fs::OpenOptions::new().read(true).open(&path)?;
+    let mut reader = io::BufReader::new(file);
+    let mut entries = read_data(&mut reader)?;
+
+    let origin = entries.origin();
+    let size = entries.size();
+    let chunk = mc.get_blocks(origin, entries.bound())?;
+
+    for entry in &mut entries {
+        let (coord, block) = entry?;
+        let current_block = chunk
+            .get_worldspace(coord)
+            .expect("Chunk should contain coordinate");
+        if block != current_block {
+            mc.set_block(coord, block)?;
+        }
+    }
+
+    fs::remove_file(&path)?;
+
+    Ok(Some((size, origin)))
+}
+
+fn list_snapshots() -> Result<Vec<PathBuf>> {
+    if !Path::new(UNDO_DIR).exists() {
+        return Ok(Vec::new());
+    }
+    let mut snapshots = Vec::new();
+    for entry in fs::read_dir(UNDO_DIR)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "dat") {
+            snapshots.push(path);
+        }
+    }
+    Ok(snapshots)
+}
+
+fn prune_old_snapshots() -> Result<()> {
+    let mut snapshots = list_snapshots()?;
+    if snapshots.len() <= MAX_SNAPSHOTS {
+        return Ok(());
+    }
+    snapshots.sort();
+    for path in &snapshots[..snapshots.len() - MAX_SNAPSHOTS] {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}